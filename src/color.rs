@@ -0,0 +1,202 @@
+use std::env;
+use trello::{Board, Card, List, TrelloObject};
+
+/// How label colors should be rendered for the current terminal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    /// 24-bit ANSI escapes, one RGB value per Trello label color.
+    Truecolor,
+    /// The nearest of the 16 standard ANSI colors, for terminals that
+    /// advertise no truecolor support.
+    Ansi16,
+    /// No escapes at all.
+    None,
+}
+
+/// Works out how (or whether) to color output, honouring `--no-color`,
+/// the `NO_COLOR` convention (https://no-color.org), and `COLORTERM` to
+/// detect truecolor-capable terminals.
+pub fn color_mode(no_color_flag: bool) -> ColorMode {
+    if no_color_flag || env::var_os("NO_COLOR").is_some() {
+        return ColorMode::None;
+    }
+
+    match env::var("COLORTERM") {
+        Ok(val) if val == "truecolor" || val == "24bit" => ColorMode::Truecolor,
+        _ => ColorMode::Ansi16,
+    }
+}
+
+/// RGB values for each color Trello offers on a label, plus the custom hex
+/// colors the API returns for boards using the newer label picker.
+fn label_rgb(color: &str) -> Option<(u8, u8, u8)> {
+    match color {
+        "green" => Some((97, 189, 79)),
+        "yellow" => Some((242, 214, 73)),
+        "orange" => Some((235, 137, 58)),
+        "red" => Some((235, 95, 86)),
+        "purple" => Some((196, 123, 226)),
+        "blue" => Some((0, 121, 191)),
+        "sky" => Some((0, 193, 224)),
+        "lime" => Some((81, 232, 137)),
+        "pink" => Some((255, 120, 203)),
+        "black" => Some((76, 89, 101)),
+        hex if hex.len() == 7 && hex.starts_with('#') => {
+            let r = u8::from_str_radix(&hex[1..3], 16).ok()?;
+            let g = u8::from_str_radix(&hex[3..5], 16).ok()?;
+            let b = u8::from_str_radix(&hex[5..7], 16).ok()?;
+            Some((r, g, b))
+        }
+        _ => None,
+    }
+}
+
+/// The closest 16-color ANSI background code for a Trello label color, used
+/// when the terminal isn't truecolor-capable.
+fn label_ansi16(color: &str) -> Option<u8> {
+    match color {
+        "green" | "lime" => Some(42),
+        "yellow" | "orange" => Some(43),
+        "red" | "pink" => Some(41),
+        "purple" => Some(45),
+        "blue" | "sky" => Some(44),
+        "black" => Some(100),
+        _ => None,
+    }
+}
+
+/// Wraps `text` in the ANSI escape sequence for a Trello label `color`,
+/// downgrading to the 16-color palette (or plain text) as `mode` dictates.
+/// Unrecognised colors (custom hex colors under `Ansi16`, or a name we
+/// don't map) are returned unchanged rather than failing.
+pub fn colorize(text: &str, color: &str, mode: ColorMode) -> String {
+    match mode {
+        ColorMode::None => text.to_string(),
+        ColorMode::Truecolor => match label_rgb(color) {
+            Some((r, g, b)) => format!("\x1b[48;2;{};{};{}m\x1b[30m {} \x1b[0m", r, g, b, text),
+            None => text.to_string(),
+        },
+        ColorMode::Ansi16 => match label_ansi16(color) {
+            Some(bg) => format!("\x1b[{}m\x1b[30m {} \x1b[0m", bg, text),
+            None => text.to_string(),
+        },
+    }
+}
+
+/// Renders a card's labels as colored, space-separated chips, e.g.
+/// ` bug ` on a red background.
+pub fn render_labels(card: &Card, mode: ColorMode) -> String {
+    card.labels
+        .iter()
+        .map(|l| colorize(&l.name, &l.color, mode))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Appends a `  <card name>: <label chips>` line (indented by two spaces)
+/// per card that has labels,
+/// skipped entirely under `ColorMode::None` so output is byte-for-byte the
+/// same as plain `render()` with `--no-color`/`NO_COLOR` set.
+fn append_label_chips<'a>(out: &mut String, cards: impl Iterator<Item = &'a Card>, mode: ColorMode) {
+    if mode == ColorMode::None {
+        return;
+    }
+
+    for card in cards {
+        let labels = render_labels(card, mode);
+        if !labels.is_empty() {
+            out.push_str(&format!("  {}: {}\n", card.name, labels));
+        }
+    }
+}
+
+/// A truecolor-aware wrapper around `List::render()`. There's no `trello`
+/// crate source in this tree to check the real rendered format against
+/// (description, due date, closed state, counts, etc.), so rather than
+/// re-deriving the list's text this defers to the real `render()` and only
+/// appends a supplementary line of colored label chips per card.
+pub fn render_list(list: &List, mode: ColorMode) -> String {
+    let mut out = list.render();
+    append_label_chips(&mut out, list.cards.as_deref().unwrap_or_default().iter(), mode);
+    out
+}
+
+/// A truecolor-aware wrapper around `Board::render()`, for the same reason
+/// as [`render_list`]: defer to the real `render()` for the board/list/card
+/// text and only append label chips, rather than guessing the format.
+pub fn render_board(board: &Board, mode: ColorMode) -> String {
+    let mut out = board.render();
+
+    for list in board.lists.as_deref().unwrap_or_default() {
+        append_label_chips(&mut out, list.cards.as_deref().unwrap_or_default().iter(), mode);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_color_flag_wins_regardless_of_env() {
+        env::remove_var("NO_COLOR");
+        env::remove_var("COLORTERM");
+        assert_eq!(color_mode(true), ColorMode::None);
+    }
+
+    #[test]
+    fn no_color_env_var_is_honoured() {
+        env::set_var("NO_COLOR", "1");
+        let mode = color_mode(false);
+        env::remove_var("NO_COLOR");
+        assert_eq!(mode, ColorMode::None);
+    }
+
+    #[test]
+    fn colorterm_truecolor_is_detected() {
+        env::remove_var("NO_COLOR");
+        env::set_var("COLORTERM", "truecolor");
+        let mode = color_mode(false);
+        env::remove_var("COLORTERM");
+        assert_eq!(mode, ColorMode::Truecolor);
+    }
+
+    #[test]
+    fn missing_colorterm_falls_back_to_ansi16() {
+        env::remove_var("NO_COLOR");
+        env::remove_var("COLORTERM");
+        assert_eq!(color_mode(false), ColorMode::Ansi16);
+    }
+
+    #[test]
+    fn colorize_known_color_truecolor() {
+        assert_eq!(
+            colorize("bug", "red", ColorMode::Truecolor),
+            "\x1b[48;2;235;95;86m\x1b[30m bug \x1b[0m"
+        );
+    }
+
+    #[test]
+    fn colorize_unknown_color_truecolor_is_unchanged() {
+        assert_eq!(colorize("bug", "mystery", ColorMode::Truecolor), "bug");
+    }
+
+    #[test]
+    fn colorize_known_color_ansi16() {
+        assert_eq!(
+            colorize("bug", "red", ColorMode::Ansi16),
+            "\x1b[41m\x1b[30m bug \x1b[0m"
+        );
+    }
+
+    #[test]
+    fn colorize_unknown_color_ansi16_is_unchanged() {
+        assert_eq!(colorize("bug", "mystery", ColorMode::Ansi16), "bug");
+    }
+
+    #[test]
+    fn colorize_none_mode_is_always_plain() {
+        assert_eq!(colorize("bug", "red", ColorMode::None), "bug");
+    }
+}
@@ -1,15 +1,21 @@
+use anyhow::{Context, Result};
 use clap::ArgMatches;
-use regex::RegexBuilder;
+use regex::{Regex, RegexBuilder};
 use std::cmp::Ordering;
-use std::error::Error;
+use std::io::{self, BufRead, Write};
 use trello::{Board, Card, Client, List, TrelloObject};
 
+/// How many invalid selections the interactive picker tolerates before
+/// giving up and returning `FindError::Aborted`.
+const PICKER_MAX_ATTEMPTS: u8 = 3;
+
 #[derive(Debug, PartialEq)]
 pub enum FindError {
     Regex(regex::Error),
     Multiple(String),
     NotFound(String),
     WildCard(String),
+    Aborted(String),
 }
 
 impl std::fmt::Display for FindError {
@@ -19,17 +25,19 @@ impl std::fmt::Display for FindError {
             FindError::Multiple(msg) => write!(f, "Multiple found: {}", msg),
             FindError::NotFound(msg) => write!(f, "Not found: {}", msg),
             FindError::WildCard(msg) => write!(f, "Wildcard error: {}", msg),
+            FindError::Aborted(msg) => write!(f, "Aborted: {}", msg),
         }
     }
 }
 
 impl std::error::Error for FindError {
-    fn cause(&self) -> Option<&dyn std::error::Error> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             FindError::Regex(ref err) => Some(err),
             FindError::Multiple(_) => None,
             FindError::NotFound(_) => None,
             FindError::WildCard(_) => None,
+            FindError::Aborted(_) => None,
         }
     }
 }
@@ -43,7 +51,8 @@ impl From<regex::Error> for FindError {
 /// Searches through a collection of Trello objects and tries
 /// to match one and only one object to the name pattern provided.
 /// * If no matches are found, an Error is returned
-/// * If more than match is found, an Error is returned
+/// * If more than one match is found and stdin/stdout are a TTY, the user is
+///   prompted to pick one interactively; otherwise an Error is returned
 /// * If only one item is matched, then it is returned
 pub fn get_object_by_name<'a, T: TrelloObject>(
     objects: &'a [T],
@@ -62,6 +71,10 @@ pub fn get_object_by_name<'a, T: TrelloObject>(
     match objects.len().cmp(&1) {
         Ordering::Equal => Ok(objects.pop().unwrap()),
         Ordering::Greater => {
+            if atty::is(atty::Stream::Stdin) && atty::is(atty::Stream::Stdout) {
+                return pick_object(objects, &mut io::stdin().lock());
+            }
+
             return Err(FindError::Multiple(format!(
                 "More than one {} found. Specify a more precise filter than '{}' (Found {})",
                 T::get_type(),
@@ -83,6 +96,44 @@ pub fn get_object_by_name<'a, T: TrelloObject>(
     }
 }
 
+/// Prompts the user (on stderr) to choose one of several candidates by
+/// number, re-prompting on invalid input until `PICKER_MAX_ATTEMPTS`
+/// answers have been read in all.
+///
+/// `input` is taken as a parameter (rather than reading `io::stdin()`
+/// directly) so the retry/abort behavior can be exercised in tests.
+fn pick_object<'a, T: TrelloObject>(
+    candidates: Vec<&'a T>,
+    input: &mut dyn BufRead,
+) -> Result<&'a T, FindError> {
+    eprintln!("Multiple {} found, please choose one:", T::get_type());
+    for (i, candidate) in candidates.iter().enumerate() {
+        eprintln!("  {}) {}", i + 1, candidate.get_name());
+    }
+
+    for attempt in 1..=PICKER_MAX_ATTEMPTS {
+        eprint!("Enter a number (1-{}): ", candidates.len());
+        io::stderr().flush().ok();
+
+        let mut line = String::new();
+        input
+            .read_line(&mut line)
+            .map_err(|e| FindError::Aborted(e.to_string()))?;
+
+        match line.trim().parse::<usize>() {
+            Ok(n) if n >= 1 && n <= candidates.len() => return Ok(candidates[n - 1]),
+            _ if attempt < PICKER_MAX_ATTEMPTS => {
+                eprintln!("Invalid selection '{}', please try again.", line.trim())
+            }
+            _ => {}
+        }
+    }
+
+    Err(FindError::Aborted(
+        "Too many invalid selections".to_string(),
+    ))
+}
+
 #[derive(Debug, PartialEq)]
 pub struct TrelloResult {
     pub board: Option<Board>,
@@ -95,6 +146,7 @@ pub struct TrelloParams<'a> {
     pub board_name: Option<&'a str>,
     pub list_name: Option<&'a str>,
     pub card_name: Option<&'a str>,
+    pub label: Option<&'a str>,
     pub ignore_case: bool,
 }
 
@@ -103,14 +155,45 @@ pub fn get_trello_params<'a>(matches: &'a ArgMatches) -> TrelloParams<'a> {
         board_name: matches.value_of("board_name"),
         list_name: matches.value_of("list_name"),
         card_name: matches.value_of("card_name"),
-        ignore_case: !matches.is_present("case_sensitive"),
+        label: matches.value_of("label"),
+        // Behavior change: the baseline read a `case_sensitive` arg that
+        // was never registered with clap, so this was always `true` and
+        // every search across `show`/`close`/`create` was silently
+        // case-insensitive regardless of `-i`. Search is now case-sensitive
+        // by default, matching what `-i`/`--ignore-case` actually documents.
+        ignore_case: matches.is_present("ignore_case"),
     }
 }
 
-pub fn get_trello_object(
-    client: &Client,
-    params: &TrelloParams,
-) -> Result<TrelloResult, Box<dyn Error>> {
+/// Whether a label's name or color matches `re`.
+fn label_matches(name: &str, color: &str, re: &Regex) -> bool {
+    re.is_match(name) || re.is_match(color)
+}
+
+/// Narrows a collection of cards down to those carrying at least one label
+/// whose name or color matches `label` (a regex). When `label` is `None`
+/// the cards are returned unfiltered.
+fn filter_cards_by_label(
+    cards: Vec<Card>,
+    label: Option<&str>,
+    ignore_case: bool,
+) -> Result<Vec<Card>, FindError> {
+    let pattern = match label {
+        Some(pattern) => pattern,
+        None => return Ok(cards),
+    };
+
+    let re = RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()?;
+
+    Ok(cards
+        .into_iter()
+        .filter(|c| c.labels.iter().any(|l| label_matches(&l.name, &l.color, &re)))
+        .collect())
+}
+
+pub fn get_trello_object(client: &Client, params: &TrelloParams) -> Result<TrelloResult> {
     let board_name = match params.board_name {
         Some(bn) => bn,
         None => {
@@ -121,13 +204,17 @@ pub fn get_trello_object(
             })
         }
     };
-    let boards = Board::get_all(&client)?;
-    let mut board = get_object_by_name(&boards, &board_name, params.ignore_case)?.clone();
+    let boards = Board::get_all(&client).context("Failed to retrieve boards from Trello")?;
+    let mut board = get_object_by_name(&boards, &board_name, params.ignore_case)
+        .with_context(|| format!("Failed to resolve board '{}'", board_name))?
+        .clone();
 
     // This should retrieve everything at once
     // This means better performance as it's less HTTP requests. But it does
     // mean we might retrieve more than we actually need in memory.
-    board.retrieve_nested(client)?;
+    board
+        .retrieve_nested(client)
+        .with_context(|| format!("Failed to retrieve lists/cards for board '{}'", board.name))?;
 
     if let Some("-") = params.list_name {
         if let Some(card_name) = params.card_name {
@@ -139,7 +226,10 @@ pub fn get_trello_object(
                 .map(|l| l.cards.unwrap())
                 .flatten()
                 .collect::<Vec<Card>>();
-            let card = get_object_by_name(&cards, &card_name, params.ignore_case)?;
+            let cards = filter_cards_by_label(cards, params.label, params.ignore_case)
+                .context("Failed to filter cards by label")?;
+            let card = get_object_by_name(&cards, &card_name, params.ignore_case)
+                .with_context(|| format!("Failed to resolve card '{}'", card_name))?;
 
             return Ok(TrelloResult {
                 board: Some(board_out),
@@ -147,18 +237,25 @@ pub fn get_trello_object(
                 card: Some(card.clone()),
             });
         } else {
-            Err(Box::new(FindError::WildCard(
+            Err(FindError::WildCard(
                 "Card name must be specified with list '-' wildcard".to_string(),
-            )))
+            )
+            .into())
         }
     } else if let Some(list_name) = params.list_name {
         let lists = &board.lists.as_ref().unwrap();
-        let list = get_object_by_name(lists, &list_name, params.ignore_case)?.clone();
+        let list = get_object_by_name(lists, &list_name, params.ignore_case)
+            .with_context(|| format!("Failed to resolve list '{}'", list_name))?
+            .clone();
 
         if let Some(card_name) = params.card_name {
-            let cards = &list.cards.as_ref().unwrap();
+            let cards = list.cards.clone().unwrap();
+            let cards = filter_cards_by_label(cards, params.label, params.ignore_case)
+                .context("Failed to filter cards by label")?;
 
-            let card = get_object_by_name(&cards, &card_name, params.ignore_case)?.clone();
+            let card = get_object_by_name(&cards, &card_name, params.ignore_case)
+                .with_context(|| format!("Failed to resolve card '{}'", card_name))?
+                .clone();
             return Ok(TrelloResult {
                 board: Some(board),
                 list: Some(list),
@@ -179,3 +276,63 @@ pub fn get_trello_object(
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_matches_color_not_name() {
+        let re = RegexBuilder::new("red").build().unwrap();
+        assert!(label_matches("bug", "red", &re));
+        assert!(!label_matches("bug", "blue", &re));
+    }
+
+    #[test]
+    fn label_matches_is_case_insensitive_when_asked() {
+        let re = RegexBuilder::new("RED").case_insensitive(true).build().unwrap();
+        assert!(label_matches("bug", "red", &re));
+    }
+
+    #[test]
+    fn label_matches_is_case_sensitive_by_default() {
+        let re = RegexBuilder::new("RED").build().unwrap();
+        assert!(!label_matches("bug", "red", &re));
+    }
+
+    #[test]
+    fn pick_object_accepts_a_valid_index() {
+        let one = Card::new("1", "Card One", "");
+        let two = Card::new("2", "Card Two", "");
+        let mut input: &[u8] = b"2\n";
+
+        let picked = pick_object(vec![&one, &two], &mut input).unwrap();
+
+        assert_eq!(picked.name, "Card Two");
+    }
+
+    #[test]
+    fn pick_object_reprompts_once_then_accepts() {
+        let one = Card::new("1", "Card One", "");
+        let two = Card::new("2", "Card Two", "");
+        let mut input: &[u8] = b"not a number\n1\n";
+
+        let picked = pick_object(vec![&one, &two], &mut input).unwrap();
+
+        assert_eq!(picked.name, "Card One");
+    }
+
+    #[test]
+    fn pick_object_aborts_after_too_many_invalid_selections() {
+        let one = Card::new("1", "Card One", "");
+        let two = Card::new("2", "Card Two", "");
+        let mut input: &[u8] = b"a\nb\nc\nd\n";
+
+        let err = pick_object(vec![&one, &two], &mut input).unwrap_err();
+
+        assert_eq!(
+            err,
+            FindError::Aborted("Too many invalid selections".to_string())
+        );
+    }
+}